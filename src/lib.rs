@@ -67,6 +67,21 @@ pub trait ContainsChar {
     fn union<T: ContainsChar>(self, other: T) -> Union<Self, T> where Self: Sized {
         Union::new(self, other)
     }
+
+    /// Combinator for creating intersections of the sets.
+    fn intersection<T: ContainsChar>(self, other: T) -> Intersection<Self, T> where Self: Sized {
+        Intersection::new(self, other)
+    }
+
+    /// Combinator for creating the set of chars contained in `self` but not in `other`.
+    fn difference<T: ContainsChar>(self, other: T) -> Difference<Self, T> where Self: Sized {
+        Difference::new(self, other)
+    }
+
+    /// Combinator for creating the complement of the set, i.e. all chars *not* contained in it.
+    fn complement(self) -> Complement<Self> where Self: Sized {
+        Complement::new(self)
+    }
 }
 
 impl<'a, T: ContainsChar + ?Sized> ContainsChar for &'a T {
@@ -111,6 +126,12 @@ impl ContainsChar for core::ops::RangeFull {
     }
 }
 
+impl ContainsChar for core::ops::RangeInclusive<char> {
+    fn contains_char(&self, c: char) -> bool {
+        c >= *self.start() && c <= *self.end()
+    }
+}
+
 #[cfg(feature = "std")]
 impl<S: std::hash::BuildHasher> ContainsChar for std::collections::HashSet<char, S> {
     fn contains_char(&self, c: char) -> bool {
@@ -146,6 +167,67 @@ impl<A: ContainsChar, B: ContainsChar> ContainsChar for Union<A, B> {
     }
 }
 
+/// Intersection of two sets of chars.
+pub struct Intersection<A: ContainsChar, B: ContainsChar> {
+    a: A,
+    b: B,
+}
+
+impl<A: ContainsChar, B: ContainsChar> Intersection<A, B> {
+    fn new(a: A, b: B) -> Self {
+        Intersection {
+            a,
+            b
+        }
+    }
+}
+
+impl<A: ContainsChar, B: ContainsChar> ContainsChar for Intersection<A, B> {
+    fn contains_char(&self, c: char) -> bool {
+        self.a.contains_char(c) && self.b.contains_char(c)
+    }
+}
+
+/// Set of chars contained in `A` but not in `B`.
+pub struct Difference<A: ContainsChar, B: ContainsChar> {
+    a: A,
+    b: B,
+}
+
+impl<A: ContainsChar, B: ContainsChar> Difference<A, B> {
+    fn new(a: A, b: B) -> Self {
+        Difference {
+            a,
+            b
+        }
+    }
+}
+
+impl<A: ContainsChar, B: ContainsChar> ContainsChar for Difference<A, B> {
+    fn contains_char(&self, c: char) -> bool {
+        self.a.contains_char(c) && !self.b.contains_char(c)
+    }
+}
+
+/// Complement of a set of chars, i.e. all chars not contained in it.
+pub struct Complement<A: ContainsChar> {
+    a: A,
+}
+
+impl<A: ContainsChar> Complement<A> {
+    fn new(a: A) -> Self {
+        Complement {
+            a,
+        }
+    }
+}
+
+impl<A: ContainsChar> ContainsChar for Complement<A> {
+    fn contains_char(&self, c: char) -> bool {
+        !self.a.contains_char(c)
+    }
+}
+
 /// Set defined by given predicate (function).
 pub struct Predicate<F: Fn(char) -> bool>(pub F);
 
@@ -186,6 +268,444 @@ impl<C: ContainsChar> fast_fmt::transform::Transform for Escaper<C> {
     }
 }
 
+/// A set of chars whose ASCII part can be represented as a fast 128-entry lookup table.
+/// This is what powers the bulk `Escaper::escape_str` fast path: scanning raw UTF-8 bytes for a
+/// special byte is only correct when every special char fits in a single ASCII byte, since
+/// continuation bytes of multi-byte UTF-8 sequences are always `>= 0x80` and must never be
+/// mistaken for a special char.
+pub trait AsciiCharSet: ContainsChar {
+    /// Builds the lookup table marking which ASCII bytes (`0..128`) are contained in the set.
+    fn ascii_table(&self) -> [bool; 128];
+
+    /// Returns `true` if the set contains only ASCII chars, i.e. `ascii_table` fully describes
+    /// the set and the bulk byte-scanning fast path may be used.
+    fn is_ascii_only(&self) -> bool;
+}
+
+impl AsciiCharSet for char {
+    fn ascii_table(&self) -> [bool; 128] {
+        let mut table = [false; 128];
+        if self.is_ascii() {
+            table[*self as usize] = true;
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        self.is_ascii()
+    }
+}
+
+impl AsciiCharSet for [char] {
+    fn ascii_table(&self) -> [bool; 128] {
+        let mut table = [false; 128];
+        for &c in self {
+            if c.is_ascii() {
+                table[c as usize] = true;
+            }
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        self.iter().all(char::is_ascii)
+    }
+}
+
+impl AsciiCharSet for core::ops::Range<char> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let mut table = [false; 128];
+        for (byte, slot) in table.iter_mut().enumerate() {
+            *slot = self.contains_char(byte as u8 as char);
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        (self.end as u32) <= 128
+    }
+}
+
+impl AsciiCharSet for core::ops::RangeTo<char> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let mut table = [false; 128];
+        for (byte, slot) in table.iter_mut().enumerate() {
+            *slot = self.contains_char(byte as u8 as char);
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        (self.end as u32) <= 128
+    }
+}
+
+impl AsciiCharSet for core::ops::RangeInclusive<char> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let mut table = [false; 128];
+        for (byte, slot) in table.iter_mut().enumerate() {
+            *slot = self.contains_char(byte as u8 as char);
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        (*self.end() as u32) < 128
+    }
+}
+
+impl<A: AsciiCharSet, B: AsciiCharSet> AsciiCharSet for Union<A, B> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let a = self.a.ascii_table();
+        let b = self.b.ascii_table();
+        let mut table = [false; 128];
+        for i in 0..128 {
+            table[i] = a[i] || b[i];
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        self.a.is_ascii_only() && self.b.is_ascii_only()
+    }
+}
+
+impl<A: AsciiCharSet, B: AsciiCharSet> AsciiCharSet for Intersection<A, B> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let a = self.a.ascii_table();
+        let b = self.b.ascii_table();
+        let mut table = [false; 128];
+        for i in 0..128 {
+            table[i] = a[i] && b[i];
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        // The intersection is a subset of both `a` and `b`, so it's ASCII-only as soon as
+        // either side is, regardless of the other.
+        self.a.is_ascii_only() || self.b.is_ascii_only()
+    }
+}
+
+impl<A: AsciiCharSet, B: AsciiCharSet> AsciiCharSet for Difference<A, B> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let a = self.a.ascii_table();
+        let b = self.b.ascii_table();
+        let mut table = [false; 128];
+        for i in 0..128 {
+            table[i] = a[i] && !b[i];
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        // `a.difference(b)` is always a subset of `a`, no matter what `b` contains.
+        self.a.is_ascii_only()
+    }
+}
+
+impl<A: AsciiCharSet> AsciiCharSet for Complement<A> {
+    fn ascii_table(&self) -> [bool; 128] {
+        let a = self.a.ascii_table();
+        let mut table = [false; 128];
+        for i in 0..128 {
+            table[i] = !a[i];
+        }
+        table
+    }
+
+    fn is_ascii_only(&self) -> bool {
+        // The complement of an ASCII-only set still contains every non-ASCII char, so it can
+        // never be ASCII-only itself.
+        false
+    }
+}
+
+impl<C: ContainsChar> Escaper<C> {
+    /// Escapes `s` into `writer`, using a bulk byte-scanning fast path when `chars` is an
+    /// ASCII-only set: the raw UTF-8 bytes of `s` are scanned for the next special byte, the
+    /// preceding unescaped slice is written in a single `write_str` call, the escape is emitted,
+    /// and the scan resumes after it. Falls back to the per-char `Transform::transform_char`
+    /// path when the set contains any non-ASCII char. `Union`, `Intersection`, `Difference` and
+    /// `Complement` all implement `AsciiCharSet` by forwarding to their operands, so combined
+    /// sets keep using the fast path as long as the combination stays ASCII-only overall.
+    pub fn escape_str<W: fast_fmt::Write>(&self, writer: &mut W, s: &str) -> Result<(), W::Error> where C: AsciiCharSet {
+        if !self.chars.is_ascii_only() {
+            return self.escape_str_slow(writer, s);
+        }
+
+        let table = self.chars.ascii_table();
+        let bytes = s.as_bytes();
+        let mut start = 0;
+
+        for (i, &b) in bytes.iter().enumerate() {
+            if b < 0x80 && table[b as usize] {
+                if start < i {
+                    writer.write_str(&s[start..i])?;
+                }
+                writer.write_char(self.escape)?;
+                writer.write_char(b as char)?;
+                start = i + 1;
+            }
+        }
+
+        if start < bytes.len() {
+            writer.write_str(&s[start..])?;
+        }
+
+        Ok(())
+    }
+
+    fn escape_str_slow<W: fast_fmt::Write>(&self, writer: &mut W, s: &str) -> Result<(), W::Error> {
+        for c in s.chars() {
+            fast_fmt::transform::Transform::transform_char(self, writer, c)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, T: fast_fmt::transform::Transform> fast_fmt::transform::Transform for &'a T {
+    fn transform_char<W: fast_fmt::Write>(&self, writer: &mut W, c: char) -> Result<(), W::Error> {
+        (**self).transform_char(writer, c)
+    }
+
+    fn transform_size_hint(&self, bytes: usize) -> usize {
+        (**self).transform_size_hint(bytes)
+    }
+}
+
+/// Wraps a value together with an `Escaper` and a flag saying whether the value is already
+/// safe, mirroring the `MarkupDisplay` idea from `askama_escape`. When marked unsafe (the
+/// default, see `new_unsafe`), the value is run through the escaper as it's written; when
+/// marked safe (`new_safe`, or via the `mark_safe`/`mark_unsafe` builders), it's forwarded
+/// verbatim. This lets
+/// trusted, already-escaped fragments be combined with untrusted ones in a single `fwrite!`
+/// call without double-escaping.
+pub struct MaybeEscaped<T, C: ContainsChar> {
+    value: T,
+    escaper: Escaper<C>,
+    safe: bool,
+}
+
+impl<T, C: ContainsChar> MaybeEscaped<T, C> {
+    /// Wraps `value`, marking it as already safe so `escaper` is never applied to it.
+    pub fn new_safe(value: T, escaper: Escaper<C>) -> Self {
+        MaybeEscaped {
+            value,
+            escaper,
+            safe: true,
+        }
+    }
+
+    /// Wraps `value`, marking it as unsafe so `escaper` is applied to it when written.
+    pub fn new_unsafe(value: T, escaper: Escaper<C>) -> Self {
+        MaybeEscaped {
+            value,
+            escaper,
+            safe: false,
+        }
+    }
+
+    /// Builder that marks the wrapped value as safe, so `escaper` is never applied to it.
+    pub fn mark_safe(mut self) -> Self {
+        self.safe = true;
+        self
+    }
+
+    /// Builder that marks the wrapped value as unsafe, so `escaper` is applied to it when
+    /// written.
+    pub fn mark_unsafe(mut self) -> Self {
+        self.safe = false;
+        self
+    }
+}
+
+impl<T: fast_fmt::Display, C: ContainsChar> fast_fmt::Display for MaybeEscaped<T, C> {
+    fn fmt<W: fast_fmt::Write>(&self, writer: &mut W) -> Result<(), W::Error> {
+        if self.safe {
+            self.value.fmt(writer)
+        } else {
+            let mut tr = writer.transform(&self.escaper);
+            self.value.fmt(&mut tr)
+        }
+    }
+}
+
+/// Reverses the effect of `Escaper`, removing the escape character from a previously escaped
+/// stream: the char right after an escape char is written out literally (even if it's the
+/// escape char itself), while every other char is passed through unchanged.
+///
+/// `Transform::transform_char` takes `&self`, so unlike `Escaper` this transform needs to
+/// remember, between calls, whether the previous char was the escape char; that single bit of
+/// state lives in a `Cell`. A lone escape char at the very end of the input is dropped silently;
+/// call `finalize` afterwards if you need to detect that case.
+pub struct Unescaper {
+    escape: char,
+    pending: core::cell::Cell<bool>,
+}
+
+impl Unescaper {
+    /// Creates the unescaper. `escape_char` is the char that was used for escaping (e.g. '\\').
+    pub fn new(escape_char: char) -> Self {
+        Unescaper {
+            escape: escape_char,
+            pending: core::cell::Cell::new(false),
+        }
+    }
+
+    /// Returns `true` if the input seen so far ended with a lone, unmatched escape char that
+    /// was dropped.
+    pub fn finalize(&self) -> bool {
+        self.pending.get()
+    }
+}
+
+impl fast_fmt::transform::Transform for Unescaper {
+    fn transform_char<W: fast_fmt::Write>(&self, writer: &mut W, c: char) -> Result<(), W::Error> {
+        if self.pending.get() {
+            self.pending.set(false);
+            writer.write_char(c)
+        } else if c == self.escape {
+            self.pending.set(true);
+            Ok(())
+        } else {
+            writer.write_char(c)
+        }
+    }
+
+    fn transform_size_hint(&self, bytes: usize) -> usize {
+        bytes
+    }
+}
+
+/// Represents a mapping from a char to the string that should replace it, used by
+/// `Substituter`.
+pub trait ReplaceChar {
+    /// Returns the replacement for `c`, or `None` if `c` should be written out unchanged.
+    fn replacement(&self, c: char) -> Option<&str>;
+}
+
+impl<'a, T: ReplaceChar + ?Sized> ReplaceChar for &'a T {
+    fn replacement(&self, c: char) -> Option<&str> {
+        (*self).replacement(c)
+    }
+}
+
+impl<'a> ReplaceChar for [(char, &'a str)] {
+    fn replacement(&self, c: char) -> Option<&str> {
+        self.iter().find(|(ch, _)| *ch == c).map(|(_, s)| *s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> ReplaceChar for std::collections::BTreeMap<char, &'a str> {
+    fn replacement(&self, c: char) -> Option<&str> {
+        self.get(&c).cloned()
+    }
+}
+
+/// Mapping defined by given function from a char to its replacement.
+pub struct ReplaceFn<F: Fn(char) -> Option<&'static str>>(pub F);
+
+impl<F: Fn(char) -> Option<&'static str>> ReplaceChar for ReplaceFn<F> {
+    fn replacement(&self, c: char) -> Option<&str> {
+        (self.0)(c)
+    }
+}
+
+/// This struct provides escaping of characters by substituting them with arbitrary strings
+/// (e.g. turning `<` into `&lt;`), unlike `Escaper` which can only prepend a single escape
+/// char.
+pub struct Substituter<M: ReplaceChar> {
+    map: M,
+    max_replacement_len: usize,
+}
+
+impl<M: ReplaceChar> Substituter<M> {
+    /// Creates the substituter.
+    /// `map` provides the replacement string for each character that should be substituted.
+    /// `max_replacement_len` must be at least as long, in bytes, as the longest string `map`
+    /// can return; it's used to size output buffers conservatively in `transform_size_hint`.
+    pub fn new(map: M, max_replacement_len: usize) -> Self {
+        Substituter {
+            map,
+            max_replacement_len,
+        }
+    }
+}
+
+impl<M: ReplaceChar> fast_fmt::transform::Transform for Substituter<M> {
+    fn transform_char<W: fast_fmt::Write>(&self, writer: &mut W, c: char) -> Result<(), W::Error> {
+        match self.map.replacement(c) {
+            Some(s) => writer.write_str(s),
+            None => writer.write_char(c),
+        }
+    }
+
+    fn transform_size_hint(&self, bytes: usize) -> usize {
+        bytes * self.max_replacement_len
+    }
+}
+
+fn write_hex_escape<W: fast_fmt::Write>(writer: &mut W, byte: u8) -> Result<(), W::Error> {
+    const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+    writer.write_str("\\x")?;
+    writer.write_char(HEX_DIGITS[(byte >> 4) as usize] as char)?;
+    writer.write_char(HEX_DIGITS[(byte & 0xf) as usize] as char)
+}
+
+/// Escapes characters the same way `core::ascii::escape_default` does: tab, carriage return,
+/// line feed, single quote, double quote and backslash become their named `\t`/`\r`/`\n`/`\'`/
+/// `\"`/`\\` escapes, printable ASCII (`0x20..=0x7e`) is passed through unchanged, and anything
+/// else is written as `\xNN` with two lowercase hex digits per UTF-8 byte of the char.
+///
+/// Unlike `core::ascii::escape_default`, which only ever escapes a single byte, this works on
+/// `char`s, so a non-ASCII char is escaped as a run of `\xNN` sequences, one per UTF-8 byte. That
+/// run is only valid to embed in a Rust string (or byte-string) literal for purely-ASCII input --
+/// `\xNN` in a `"..."` literal requires `NN <= 0x7f` -- so treat the output of non-ASCII input as
+/// byte-oriented debug output rather than a literal-safe string.
+pub struct DefaultEscaper;
+
+impl DefaultEscaper {
+    /// Creates the escaper.
+    pub fn new() -> Self {
+        DefaultEscaper
+    }
+}
+
+impl Default for DefaultEscaper {
+    fn default() -> Self {
+        DefaultEscaper::new()
+    }
+}
+
+impl fast_fmt::transform::Transform for DefaultEscaper {
+    fn transform_char<W: fast_fmt::Write>(&self, writer: &mut W, c: char) -> Result<(), W::Error> {
+        match c {
+            '\t' => writer.write_str("\\t"),
+            '\r' => writer.write_str("\\r"),
+            '\n' => writer.write_str("\\n"),
+            '\'' => writer.write_str("\\'"),
+            '"' => writer.write_str("\\\""),
+            '\\' => writer.write_str("\\\\"),
+            ' '..='~' => writer.write_char(c),
+            _ => {
+                let mut buf = [0u8; 4];
+                for &b in c.encode_utf8(&mut buf).as_bytes() {
+                    write_hex_escape(writer, b)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    fn transform_size_hint(&self, bytes: usize) -> usize {
+        bytes * 4
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ::Escaper;
@@ -232,4 +752,265 @@ mod tests {
 
         assert_eq!(s, "\\a\\bcd$\\e\\fgh");
     }
+
+    #[test]
+    fn substitution() {
+        use ::Substituter;
+
+        let entities: &[(char, &str)] = &[('<', "&lt;"), ('&', "&amp;"), ('"', "&quot;")];
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(Substituter::new(entities, 6));
+
+            fwrite!(&mut tr, "<a href=\"x\">A&B</a>").unwrap();
+        }
+
+        assert_eq!(s, "&lt;a href=&quot;x&quot;>A&amp;B&lt;/a>");
+    }
+
+    #[test]
+    fn substitution_btreemap() {
+        use ::Substituter;
+        use ::std::collections::BTreeMap;
+
+        let mut entities = BTreeMap::new();
+        entities.insert('<', "&lt;");
+        entities.insert('&', "&amp;");
+        entities.insert('"', "&quot;");
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(Substituter::new(entities, 6));
+
+            fwrite!(&mut tr, "<a href=\"x\">A&B</a>").unwrap();
+        }
+
+        assert_eq!(s, "&lt;a href=&quot;x&quot;>A&amp;B&lt;/a>");
+    }
+
+    #[test]
+    fn substitution_replace_fn() {
+        use ::{Substituter, ReplaceFn};
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let replacer = ReplaceFn(|c| match c {
+                '<' => Some("&lt;"),
+                '&' => Some("&amp;"),
+                '"' => Some("&quot;"),
+                _ => None,
+            });
+            let mut tr = s.transform(Substituter::new(replacer, 6));
+
+            fwrite!(&mut tr, "<a href=\"x\">A&B</a>").unwrap();
+        }
+
+        assert_eq!(s, "&lt;a href=&quot;x&quot;>A&amp;B&lt;/a>");
+    }
+
+    #[test]
+    fn default_escaper() {
+        use ::DefaultEscaper;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(DefaultEscaper::new());
+
+            fwrite!(&mut tr, "a\tb\r\nc\"d'e\\f\x01").unwrap();
+        }
+
+        assert_eq!(s, "a\\tb\\r\\nc\\\"d\\'e\\\\f\\x01");
+    }
+
+    #[test]
+    fn default_escaper_non_ascii_escapes_utf8_bytes() {
+        use ::DefaultEscaper;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(DefaultEscaper::default());
+
+            fwrite!(&mut tr, "\u{e9}").unwrap();
+        }
+
+        assert_eq!(s, "\\xc3\\xa9");
+    }
+
+    #[test]
+    fn escape_str_ascii_fast_path() {
+        let mut s = String::new();
+        let escaper = Escaper::new('\\', 'a'..'c');
+
+        escaper.escape_str(&mut s, "abcd$efgh").unwrap();
+
+        assert_eq!(s, "\\a\\bcd$efgh");
+    }
+
+    #[test]
+    fn escape_str_fast_path_through_combinator() {
+        use ::ContainsChar;
+
+        let mut s = String::new();
+        let escaper = Escaper::new('\\', ('a'..'c').union('e'..'g'));
+
+        escaper.escape_str(&mut s, "abcd$efgh").unwrap();
+
+        assert_eq!(s, "\\a\\bcd$\\e\\fgh");
+    }
+
+    #[test]
+    fn escape_str_fast_path_range_inclusive() {
+        let mut s = String::new();
+        let escaper = Escaper::new('\\', 'a'..='c');
+
+        escaper.escape_str(&mut s, "abcd$efgh").unwrap();
+
+        assert_eq!(s, "\\a\\b\\cd$efgh");
+    }
+
+    #[test]
+    fn escape_str_through_range_inclusive_complement() {
+        use ::ContainsChar;
+
+        // `(' '..='~').complement()` -- escaping every non-printable char -- is the motivating
+        // example for both `RangeInclusive<char>: ContainsChar` and `complement()`. `escape_str`
+        // needs `RangeInclusive<char>: AsciiCharSet` for this to type-check at all; since the
+        // complement of an ASCII-only set is never itself ASCII-only, it actually runs the
+        // per-char fallback path, but the two features must still compose.
+        let mut s = String::new();
+        let escaper = Escaper::new('\\', (' '..='~').complement());
+
+        escaper.escape_str(&mut s, "a\tb").unwrap();
+
+        assert_eq!(s, "a\\\tb");
+    }
+
+    #[test]
+    fn escape_str_non_ascii_fallback() {
+        let mut s = String::new();
+        let escaper = Escaper::new('\\', 'a'..'\u{1f600}');
+
+        escaper.escape_str(&mut s, "abcd$efgh").unwrap();
+
+        assert_eq!(s, "\\a\\b\\c\\d$\\e\\f\\g\\h");
+    }
+
+    #[test]
+    fn maybe_escaped() {
+        use ::MaybeEscaped;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let unsafe_val = MaybeEscaped::new_unsafe("a$b", Escaper::new('\\', '$'));
+            let safe_val = MaybeEscaped::new_safe("c$d", Escaper::new('\\', '$'));
+
+            fwrite!(s, unsafe_val, safe_val).unwrap();
+        }
+
+        assert_eq!(s, "a\\$bc$d");
+    }
+
+    #[test]
+    fn maybe_escaped_mark_safe_builders() {
+        use ::MaybeEscaped;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let marked_safe = MaybeEscaped::new_unsafe("a$b", Escaper::new('\\', '$')).mark_safe();
+            let marked_unsafe = MaybeEscaped::new_safe("c$d", Escaper::new('\\', '$')).mark_unsafe();
+
+            fwrite!(s, marked_safe, marked_unsafe).unwrap();
+        }
+
+        assert_eq!(s, "a$bc\\$d");
+    }
+
+    #[test]
+    fn unescape() {
+        use ::Unescaper;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let unescaper = Unescaper::new('\\');
+            let mut tr = s.transform(&unescaper);
+
+            fwrite!(&mut tr, "abcd\\$efgh\\\\i").unwrap();
+
+            assert!(!unescaper.finalize());
+        }
+
+        assert_eq!(s, "abcd$efgh\\i");
+    }
+
+    #[test]
+    fn unescape_trailing_escape_dropped() {
+        use ::Unescaper;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let unescaper = Unescaper::new('\\');
+            let mut tr = s.transform(&unescaper);
+
+            fwrite!(&mut tr, "ab\\").unwrap();
+
+            assert!(unescaper.finalize());
+        }
+
+        assert_eq!(s, "ab");
+    }
+
+    #[test]
+    fn intersection() {
+        use ::ContainsChar;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(Escaper::new('\\', ('a'..'e').intersection('c'..'g')));
+
+            fwrite!(&mut tr, "abcdefg").unwrap();
+        }
+
+        assert_eq!(s, "ab\\c\\defg");
+    }
+
+    #[test]
+    fn difference() {
+        use ::ContainsChar;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(Escaper::new('\\', ('a'..'e').difference('c'..'g')));
+
+            fwrite!(&mut tr, "abcdefg").unwrap();
+        }
+
+        assert_eq!(s, "\\a\\bcdefg");
+    }
+
+    #[test]
+    fn complement() {
+        use ::ContainsChar;
+
+        let mut s = String::new();
+        {
+            let s = &mut s;
+            let mut tr = s.transform(Escaper::new('\\', (' '..='~').complement()));
+
+            fwrite!(&mut tr, "a\tb").unwrap();
+        }
+
+        assert_eq!(s, "a\\\tb");
+    }
 }